@@ -0,0 +1,14 @@
+//! A layered hash map and hash set for representing lexical scopes, where each new scope is a
+//! layer that can shadow bindings from outer layers without losing them.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+mod map;
+mod set;
+#[cfg(any(feature = "std", feature = "alloc"))]
+mod weak;
+
+pub use map::ScopeMap;
+pub use set::ScopeSet;
+#[cfg(any(feature = "std", feature = "alloc"))]
+pub use weak::WeakScopeMap;
@@ -1,19 +1,41 @@
-use std::{
+#[cfg(not(feature = "std"))]
+extern crate alloc;
+
+use core::{
   borrow::Borrow,
-  collections::{hash_map::RandomState, HashSet},
   hash::{Hash, BuildHasher},
-  ops::Index
+  ops::Index,
 };
 
+#[cfg(feature = "std")]
+use std::collections::{hash_map::RandomState, HashSet};
+#[cfg(not(feature = "std"))]
+use hashbrown::HashSet;
+#[cfg(all(not(feature = "std"), feature = "ahash"))]
+use ahash::RandomState;
+
+#[cfg(feature = "std")]
+use std::vec::{IntoIter as VecIntoIter, Vec};
+#[cfg(not(feature = "std"))]
+use alloc::vec::{IntoIter as VecIntoIter, Vec};
+
 use indexmap::{IndexMap};
 use smallvec::{smallvec, SmallVec};
 
+#[cfg(any(feature = "std", feature = "ahash"))]
 #[derive(Clone)]
 pub struct ScopeMap<K, V, S: BuildHasher = RandomState> {
   map: IndexMap<K, SmallVec<[V; 1]>, S>,
   layers: SmallVec<[HashSet<usize>; 1]>,
 }
 
+#[cfg(not(any(feature = "std", feature = "ahash")))]
+#[derive(Clone)]
+pub struct ScopeMap<K, V, S: BuildHasher> {
+  map: IndexMap<K, SmallVec<[V; 1]>, S>,
+  layers: SmallVec<[HashSet<usize>; 1]>,
+}
+
 impl<K, V, S: Default + BuildHasher> Default for ScopeMap<K, V, S> {
   #[inline]
   fn default() -> Self {
@@ -21,10 +43,9 @@ impl<K, V, S: Default + BuildHasher> Default for ScopeMap<K, V, S> {
   }
 }
 
-impl<K, Q: ?Sized, V, S> Index<&Q> for ScopeMap<K, V, S>
-where 
+impl<K, Q: ?Sized + Eq + Hash, V, S> Index<&Q> for ScopeMap<K, V, S>
+where
   K: Eq + Hash + Borrow<Q>,
-  Q: Eq + Hash,
   S: BuildHasher,
 {
   type Output = V;
@@ -40,6 +61,17 @@ where
   }
 }
 
+impl<'a, K, V, S: BuildHasher> IntoIterator for &'a ScopeMap<K, V, S> {
+  type Item = (&'a K, &'a V);
+  type IntoIter = Iter<'a, K, V>;
+
+  #[inline]
+  fn into_iter(self) -> Self::IntoIter {
+    self.iter()
+  }
+}
+
+#[cfg(any(feature = "std", feature = "ahash"))]
 impl<K, V> ScopeMap<K, V, RandomState> {
   #[inline]
   pub fn new() -> ScopeMap<K, V, RandomState> {
@@ -94,6 +126,12 @@ impl<K, V, S: BuildHasher> ScopeMap<K, V, S> {
   pub fn layer_count(&self) -> usize {
     self.layers.len()
   }
+
+  /// Alias for [`layer_count`](Self::layer_count).
+  #[inline]
+  pub fn depth(&self) -> usize {
+    self.layer_count()
+  }
 }
 
 impl<K, V, S> ScopeMap<K, V, S> 
@@ -120,56 +158,255 @@ where
     }
     false
   }
+
+  /// Returns an iterator over the keys and their topmost visible values, in insertion order.
+  #[inline]
+  pub fn iter(&self) -> Iter<'_, K, V> {
+    Iter { inner: self.map.iter() }
+  }
+
+  /// Returns an iterator over the keys whose topmost visible value is live, in insertion order.
+  #[inline]
+  pub fn keys(&self) -> Keys<'_, K, V> {
+    Keys { inner: self.iter() }
+  }
+
+  /// Returns an iterator over the topmost visible values, in key insertion order.
+  #[inline]
+  pub fn values(&self) -> Values<'_, K, V> {
+    Values { inner: self.iter() }
+  }
+
+  /// Returns a mutable iterator over the topmost visible values, in key insertion order.
+  #[inline]
+  pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
+    ValuesMut { inner: self.map.iter_mut() }
+  }
+
+  /// Returns an iterator over the keys and values defined at the topmost layer.
+  #[inline]
+  pub fn iter_top(&self) -> IterTop<'_, K, V, S> {
+    self.iter_layer(0)
+  }
+
+  /// Returns an iterator over the keys and values defined at the layer `depth` levels below the top.
+  /// A depth of 0 is the topmost layer.
+  #[inline]
+  pub fn iter_layer(&self, depth: usize) -> IterLayer<'_, K, V, S> {
+    let mut entries: Vec<(usize, usize)> = match self.layers.iter().rev().nth(depth) {
+      Some(layer) => layer
+        .iter()
+        .map(|&index| {
+          // The value a key has *at this layer* sits as many slots down from the top of its
+          // stack as there are layers, from the top through this one, that also define it.
+          let stack_pos_from_top = self.layers
+            .iter()
+            .rev()
+            .take(depth + 1)
+            .filter(|layer| layer.contains(&index))
+            .count() - 1;
+          (index, stack_pos_from_top)
+        })
+        .collect(),
+      None => Vec::new(),
+    };
+    entries.sort_unstable_by_key(|&(index, _)| index);
+    IterLayer { map: &self.map, entries: entries.into_iter() }
+  }
+}
+
+/// An iterator over the keys and topmost visible values of a [`ScopeMap`], in insertion order.
+pub struct Iter<'a, K, V> {
+  inner: indexmap::map::Iter<'a, K, SmallVec<[V; 1]>>,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+  type Item = (&'a K, &'a V);
+
+  #[inline]
+  fn next(&mut self) -> Option<Self::Item> {
+    self.inner.find_map(|(k, stack)| stack.last().map(|v| (k, v)))
+  }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Iter<'a, K, V> {
+  #[inline]
+  fn next_back(&mut self) -> Option<Self::Item> {
+    loop {
+      let (k, stack) = self.inner.next_back()?;
+      if let Some(v) = stack.last() {
+        return Some((k, v));
+      }
+    }
+  }
+}
+
+/// An iterator over the keys of a [`ScopeMap`] whose topmost visible value is live, in insertion order.
+pub struct Keys<'a, K, V> {
+  inner: Iter<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for Keys<'a, K, V> {
+  type Item = &'a K;
+
+  #[inline]
+  fn next(&mut self) -> Option<Self::Item> {
+    self.inner.next().map(|(k, _)| k)
+  }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Keys<'a, K, V> {
+  #[inline]
+  fn next_back(&mut self) -> Option<Self::Item> {
+    self.inner.next_back().map(|(k, _)| k)
+  }
+}
+
+/// An iterator over the topmost visible values of a [`ScopeMap`], in key insertion order.
+pub struct Values<'a, K, V> {
+  inner: Iter<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for Values<'a, K, V> {
+  type Item = &'a V;
+
+  #[inline]
+  fn next(&mut self) -> Option<Self::Item> {
+    self.inner.next().map(|(_, v)| v)
+  }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Values<'a, K, V> {
+  #[inline]
+  fn next_back(&mut self) -> Option<Self::Item> {
+    self.inner.next_back().map(|(_, v)| v)
+  }
+}
+
+/// A mutable iterator over the topmost visible values of a [`ScopeMap`], in key insertion order.
+pub struct ValuesMut<'a, K, V> {
+  inner: indexmap::map::IterMut<'a, K, SmallVec<[V; 1]>>,
+}
+
+impl<'a, K, V> Iterator for ValuesMut<'a, K, V> {
+  type Item = &'a mut V;
+
+  #[inline]
+  fn next(&mut self) -> Option<Self::Item> {
+    loop {
+      let (_k, stack) = self.inner.next()?;
+      if let Some(v) = stack.last_mut() {
+        return Some(v);
+      }
+    }
+  }
+}
+
+impl<'a, K, V> DoubleEndedIterator for ValuesMut<'a, K, V> {
+  #[inline]
+  fn next_back(&mut self) -> Option<Self::Item> {
+    loop {
+      let (_k, stack) = self.inner.next_back()?;
+      if let Some(v) = stack.last_mut() {
+        return Some(v);
+      }
+    }
+  }
+}
+
+/// An iterator over the keys and values defined at a single layer of a [`ScopeMap`].
+pub struct IterLayer<'a, K, V, S> {
+  map: &'a IndexMap<K, SmallVec<[V; 1]>, S>,
+  // (stack index, position from the top of that key's value stack)
+  entries: VecIntoIter<(usize, usize)>,
+}
+
+impl<'a, K, V, S> Iterator for IterLayer<'a, K, V, S> {
+  type Item = (&'a K, &'a V);
+
+  #[inline]
+  fn next(&mut self) -> Option<Self::Item> {
+    let (index, stack_pos_from_top) = self.entries.next()?;
+    let (k, stack) = self.map.get_index(index)?;
+    stack.iter().rev().nth(stack_pos_from_top).map(|v| (k, v))
+  }
+}
+
+impl<'a, K, V, S> DoubleEndedIterator for IterLayer<'a, K, V, S> {
+  #[inline]
+  fn next_back(&mut self) -> Option<Self::Item> {
+    let (index, stack_pos_from_top) = self.entries.next_back()?;
+    let (k, stack) = self.map.get_index(index)?;
+    stack.iter().rev().nth(stack_pos_from_top).map(|v| (k, v))
+  }
+}
+
+/// An iterator over the keys and values defined at the topmost layer of a [`ScopeMap`].
+pub type IterTop<'a, K, V, S> = IterLayer<'a, K, V, S>;
+
+/// An iterator over every live binding of a key, paired with the layer depth (0 = top) at
+/// which each is visible. See [`ScopeMap::iter_all`].
+pub struct IterAll<'a, V> {
+  depths: VecIntoIter<usize>,
+  values: core::iter::Rev<core::slice::Iter<'a, V>>,
+}
+
+impl<'a, V> Iterator for IterAll<'a, V> {
+  type Item = (usize, &'a V);
+
+  #[inline]
+  fn next(&mut self) -> Option<Self::Item> {
+    Some((self.depths.next()?, self.values.next()?))
+  }
 }
 
 impl<K: Eq + Hash, V, S: BuildHasher> ScopeMap<K, V, S> {
   
   /// Returns `true` if the map contains the specified key in any layer.
+  ///
+  /// A key whose value stack has been emptied by [`delete`](Self::delete)/[`pop_layer`](Self::pop_layer)
+  /// still has an entry in the underlying map, so this checks for a live value rather than mere
+  /// key presence.
   #[inline]
-  pub fn contains_key<Q: ?Sized>(&self, key: &Q) -> bool
+  pub fn contains_key<Q: ?Sized + Eq + Hash>(&self, key: &Q) -> bool
   where
     K: Borrow<Q>,
-    Q: Eq + Hash,
   {
-    self.map.contains_key(key)
-  } 
+    self.get(key).is_some()
+  }
 
   /// Returns `true` if the map contains the specified key at the top layer.
   #[inline]
-  pub fn contains_key_at_top<Q: ?Sized>(&self, key: &Q) -> bool
+  pub fn contains_key_at_top<Q: ?Sized + Eq + Hash>(&self, key: &Q) -> bool
   where
     K: Borrow<Q>,
-    Q: Eq + Hash,
   {
-    self.map.get_full(key).map_or(false, |(index, ..)| self.layers.last().unwrap().contains(&index))
+    self.map.get_full(key).is_some_and(|(index, ..)| self.layers.last().unwrap().contains(&index))
   }
-  
+
   /// Gets a reference to the topmost value associated with a key.
   #[inline]
-  pub fn get<Q: ?Sized>(&self, key: &Q) -> Option<&V>
+  pub fn get<Q: ?Sized + Eq + Hash>(&self, key: &Q) -> Option<&V>
   where
   K: Borrow<Q>,
-  Q: Eq + Hash,
   {
     self.map.get(key).and_then(|v| v.last())
   }
-  
+
   /// Gets a mutable reference to the topmost value associated with a key.
   #[inline]
-  pub fn get_mut<Q: ?Sized>(&mut self, key: &Q) -> Option<&mut V>
+  pub fn get_mut<Q: ?Sized + Eq + Hash>(&mut self, key: &Q) -> Option<&mut V>
   where
   K: Borrow<Q>,
-  Q: Eq + Hash,
   {
     self.map.get_mut(key).and_then(|v| v.last_mut())
   }
-  
+
   /// Gets a reference to a value `skip_count` layers below the topmost value associated with a key.
   #[inline]
-  pub fn get_parent<Q: ?Sized>(&self, key: &Q, skip_count: usize) -> Option<&V>
+  pub fn get_parent<Q: ?Sized + Eq + Hash>(&self, key: &Q, skip_count: usize) -> Option<&V>
   where
   K: Borrow<Q>,
-  Q: Eq + Hash,
   {
     if let Some((stack_index, _key, stack)) = self.map.get_full(key) {
       // If the skip count exceeds the stack size, it shouldn't matter because take() is self-truncating
@@ -184,13 +421,12 @@ impl<K: Eq + Hash, V, S: BuildHasher> ScopeMap<K, V, S> {
     }
     None
   }
-  
+
   /// Gets a mutable reference to a value `skip_count` layers below the topmost value associated with a key.
   #[inline]
-  pub fn get_parent_mut<Q: ?Sized>(&mut self, key: &Q, skip_count: usize) -> Option<&mut V>
+  pub fn get_parent_mut<Q: ?Sized + Eq + Hash>(&mut self, key: &Q, skip_count: usize) -> Option<&mut V>
   where
     K: Borrow<Q>,
-    Q: Eq + Hash,
   {
     if let Some((stack_index, _key, stack)) = self.map.get_full_mut(key) {
       // If the skip count exceeds the stack size, it shouldn't matter because take() is self-truncating
@@ -205,13 +441,84 @@ impl<K: Eq + Hash, V, S: BuildHasher> ScopeMap<K, V, S> {
     }
     None
   }
-  
+
+  /// Gets the depth of the specified key (i.e. how many layers down the key is).
+  /// A depth of 0 means that the topmost layer contains the key.
+  ///
+  /// Returns `None` if the key does not exist.
+  ///
+  /// Computes in **O(n)** time (worst-case) with respect to layer count.
+  #[inline]
+  pub fn depth_of<Q: ?Sized + Eq + Hash>(&self, key: &Q) -> Option<usize>
+  where
+    K: Borrow<Q>,
+  {
+    let (stack_index, ..) = self.map.get_full(key)?;
+    self.layers.iter().rev().position(|layer| layer.contains(&stack_index))
+  }
+
+  /// Returns how many times the key is currently shadowed, i.e. the number of live bindings
+  /// below the topmost one. Returns `0` for keys that are not shadowed or do not exist.
+  #[inline]
+  pub fn shadow_count<Q: ?Sized + Eq + Hash>(&self, key: &Q) -> usize
+  where
+    K: Borrow<Q>,
+  {
+    self.map.get(key).map_or(0, |stack| stack.len().saturating_sub(1))
+  }
+
+  /// Iterates over every live binding of `key` across the stack, paired with the layer depth
+  /// (0 = top) at which each is visible. This generalizes [`get_parent`](Self::get_parent) and
+  /// [`depth_of`](Self::depth_of) into a single walk, e.g. for "which enclosing scopes define
+  /// `x`" diagnostics or closure capture analysis.
+  #[inline]
+  pub fn iter_all<Q: ?Sized + Eq + Hash>(&self, key: &Q) -> IterAll<'_, V>
+  where
+    K: Borrow<Q>,
+  {
+    if let Some((stack_index, _key, stack)) = self.map.get_full(key) {
+      let depths: Vec<usize> = self.layers
+        .iter()
+        .rev()
+        .enumerate()
+        .filter(|(_, layer)| layer.contains(&stack_index))
+        .map(|(depth, _)| depth)
+        .collect();
+      IterAll { depths: depths.into_iter(), values: stack.iter().rev() }
+    } else {
+      IterAll { depths: Vec::new().into_iter(), values: (&[] as &[V]).iter().rev() }
+    }
+  }
+
+  /// Gets the entry for the given key at the topmost layer, allowing in-place manipulation
+  /// without a separate lookup to check for shadowing.
+  ///
+  /// If the key is only defined in a lower layer, the entry is `Vacant`; inserting into it
+  /// pushes a new value onto the key's stack and records it in the topmost layer, exactly as
+  /// [`define`](Self::define) does.
+  #[inline]
+  pub fn entry(&mut self, key: K) -> Entry<'_, K, V> {
+    let top_layer = self.layers.last_mut().unwrap();
+    match self.map.entry(key) {
+      indexmap::map::Entry::Occupied(o) => {
+        if top_layer.contains(&o.index()) {
+          Entry::Occupied(OccupiedEntry { inner: o })
+        } else {
+          Entry::Vacant(VacantEntry { slot: VacantSlot::Shadowed(o), top_layer })
+        }
+      }
+      indexmap::map::Entry::Vacant(v) => {
+        Entry::Vacant(VacantEntry { slot: VacantSlot::New(v), top_layer })
+      }
+    }
+  }
+
   /// Adds a value to the topmost layer.
   #[inline]
   pub fn define(&mut self, key: K, value: V) {
     let entry = self.map.entry(key);
     let stack_index = entry.index();
-    let stack = entry.or_insert_with(Default::default);    
+    let stack = entry.or_default();
     let is_new = self.layers.last_mut().unwrap().insert(stack_index);
     
     if is_new {
@@ -248,4 +555,351 @@ impl<K: Eq + Hash, V, S: BuildHasher> ScopeMap<K, V, S> {
     self.layers.clear();
     self.layers.push(Default::default())
   }
+}
+
+/// A view into a single entry of a [`ScopeMap`]'s topmost layer, obtained via [`ScopeMap::entry`].
+pub enum Entry<'a, K, V> {
+  Occupied(OccupiedEntry<'a, K, V>),
+  Vacant(VacantEntry<'a, K, V>),
+}
+
+impl<'a, K, V> Entry<'a, K, V> {
+  /// Returns a reference to the entry's key.
+  #[inline]
+  pub fn key(&self) -> &K {
+    match self {
+      Entry::Occupied(entry) => entry.key(),
+      Entry::Vacant(entry) => entry.key(),
+    }
+  }
+
+  /// Ensures a value is present at the topmost layer, inserting `default` if vacant, and
+  /// returns a mutable reference to the value.
+  #[inline]
+  pub fn or_insert(self, default: V) -> &'a mut V {
+    match self {
+      Entry::Occupied(entry) => entry.into_mut(),
+      Entry::Vacant(entry) => entry.insert(default),
+    }
+  }
+
+  /// Ensures a value is present at the topmost layer, inserting the result of `default` if
+  /// vacant, and returns a mutable reference to the value.
+  #[inline]
+  pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+    match self {
+      Entry::Occupied(entry) => entry.into_mut(),
+      Entry::Vacant(entry) => entry.insert(default()),
+    }
+  }
+
+  /// Calls `f` with a mutable reference to the value if the entry is occupied at the top layer,
+  /// then returns the entry unchanged.
+  #[inline]
+  pub fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Self {
+    match self {
+      Entry::Occupied(mut entry) => {
+        f(entry.get_mut());
+        Entry::Occupied(entry)
+      }
+      Entry::Vacant(entry) => Entry::Vacant(entry),
+    }
+  }
+}
+
+impl<'a, K, V: Default> Entry<'a, K, V> {
+  /// Ensures a value is present at the topmost layer, inserting `V::default()` if vacant, and
+  /// returns a mutable reference to the value.
+  #[inline]
+  pub fn or_default(self) -> &'a mut V {
+    match self {
+      Entry::Occupied(entry) => entry.into_mut(),
+      Entry::Vacant(entry) => entry.insert(Default::default()),
+    }
+  }
+}
+
+/// An occupied entry in the topmost layer of a [`ScopeMap`].
+pub struct OccupiedEntry<'a, K, V> {
+  inner: indexmap::map::OccupiedEntry<'a, K, SmallVec<[V; 1]>>,
+}
+
+impl<'a, K, V> OccupiedEntry<'a, K, V> {
+  /// Returns a reference to the entry's key.
+  #[inline]
+  pub fn key(&self) -> &K {
+    self.inner.key()
+  }
+
+  /// Returns a reference to the value at the topmost layer.
+  #[inline]
+  pub fn get(&self) -> &V {
+    self.inner.get().last().expect("occupied entry must have a value on top")
+  }
+
+  /// Returns a mutable reference to the value at the topmost layer.
+  #[inline]
+  pub fn get_mut(&mut self) -> &mut V {
+    self.inner.get_mut().last_mut().expect("occupied entry must have a value on top")
+  }
+
+  /// Converts into a mutable reference to the value at the topmost layer, bound to the
+  /// lifetime of the original [`ScopeMap`] borrow.
+  #[inline]
+  pub fn into_mut(self) -> &'a mut V {
+    self.inner.into_mut().last_mut().expect("occupied entry must have a value on top")
+  }
+
+  /// Replaces the value at the topmost layer, returning the previous value.
+  #[inline]
+  pub fn insert(&mut self, value: V) -> V {
+    core::mem::replace(self.get_mut(), value)
+  }
+}
+
+/// The underlying slot a [`VacantEntry`] will insert into.
+enum VacantSlot<'a, K, V> {
+  /// The key does not exist in any layer yet.
+  New(indexmap::map::VacantEntry<'a, K, SmallVec<[V; 1]>>),
+  /// The key exists in a lower layer but is not yet shadowed at the top.
+  Shadowed(indexmap::map::OccupiedEntry<'a, K, SmallVec<[V; 1]>>),
+}
+
+/// A vacant entry in the topmost layer of a [`ScopeMap`].
+pub struct VacantEntry<'a, K, V> {
+  slot: VacantSlot<'a, K, V>,
+  top_layer: &'a mut HashSet<usize>,
+}
+
+impl<'a, K, V> VacantEntry<'a, K, V> {
+  /// Returns a reference to the entry's key.
+  #[inline]
+  pub fn key(&self) -> &K {
+    match &self.slot {
+      VacantSlot::New(entry) => entry.key(),
+      VacantSlot::Shadowed(entry) => entry.key(),
+    }
+  }
+
+  /// Inserts a value into the topmost layer, returning a mutable reference to it.
+  #[inline]
+  pub fn insert(self, value: V) -> &'a mut V {
+    match self.slot {
+      VacantSlot::New(entry) => {
+        let index = entry.index();
+        self.top_layer.insert(index);
+        entry.insert(smallvec![value]).last_mut().unwrap()
+      }
+      VacantSlot::Shadowed(entry) => {
+        self.top_layer.insert(entry.index());
+        let stack = entry.into_mut();
+        stack.push(value);
+        stack.last_mut().unwrap()
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  #[cfg(not(feature = "std"))]
+  use alloc::vec;
+
+  #[test]
+  fn map_define_and_get() {
+    let mut map = ScopeMap::new();
+    map.define("foo", 1);
+    assert_eq!(Some(&1), map.get("foo"));
+  }
+
+  #[test]
+  fn map_iter() {
+    let mut map = ScopeMap::new();
+    map.define("foo", 1);
+    map.push_layer();
+    map.define("bar", 2);
+    let mut items: Vec<(&&str, &i32)> = map.iter().collect();
+    items.sort_unstable();
+    assert_eq!(vec![(&"bar", &2), (&"foo", &1)], items);
+  }
+
+  #[test]
+  fn map_iter_top_only_sees_top_layer() {
+    let mut map = ScopeMap::new();
+    map.define("foo", 1);
+    map.push_layer();
+    map.define("bar", 2);
+    let top: Vec<(&&str, &i32)> = map.iter_top().collect();
+    assert_eq!(vec![(&"bar", &2)], top);
+  }
+
+  #[test]
+  fn map_iter_layer_sees_shadowed_value_at_that_layer() {
+    let mut map = ScopeMap::new();
+    map.define("x", 1);
+    map.push_layer();
+    map.define("x", 2);
+    assert_eq!(vec![(&"x", &1)], map.iter_layer(1).collect::<Vec<_>>());
+    assert_eq!(vec![(&"x", &2)], map.iter_layer(0).collect::<Vec<_>>());
+  }
+
+  #[test]
+  fn map_entry_or_insert_on_vacant() {
+    let mut map: ScopeMap<&str, i32> = ScopeMap::new();
+    *map.entry("foo").or_insert(1) += 1;
+    assert_eq!(Some(&2), map.get("foo"));
+  }
+
+  #[test]
+  fn map_entry_is_occupied_only_at_top_layer() {
+    let mut map = ScopeMap::new();
+    map.define("foo", 1);
+    map.push_layer();
+    // "foo" is only defined in a lower layer, so at the top it's still Vacant.
+    match map.entry("foo") {
+      Entry::Vacant(entry) => {
+        entry.insert(2);
+      }
+      Entry::Occupied(_) => panic!("expected a vacant entry at the top layer"),
+    }
+    assert_eq!(Some(&2), map.get("foo"));
+    map.pop_layer();
+    assert_eq!(Some(&1), map.get("foo"));
+  }
+
+  #[test]
+  fn map_shadow_count_and_iter_all() {
+    let mut map = ScopeMap::new();
+    map.define("x", 1);
+    map.push_layer();
+    map.define("x", 2);
+    assert_eq!(1, map.shadow_count("x"));
+    assert_eq!(0, map.shadow_count("y"));
+    assert_eq!(vec![(0, &2), (1, &1)], map.iter_all("x").collect::<Vec<_>>());
+  }
+}
+
+/// Serializes and deserializes the full layered structure of a [`ScopeMap`], not just the
+/// flattened topmost view, so an in-progress scope environment can be snapshotted and reloaded.
+#[cfg(feature = "serde")]
+mod serde_support {
+  use super::*;
+  use serde::{
+    de::{SeqAccess, Visitor},
+    ser::SerializeMap,
+    Deserialize, Deserializer, Serialize, Serializer,
+  };
+  use core::{fmt, marker::PhantomData};
+  #[cfg(not(feature = "std"))]
+  use alloc::vec;
+
+  struct SerLayer<'a, K, V>(Vec<(&'a K, &'a V)>);
+
+  impl<'a, K: Serialize, V: Serialize> Serialize for SerLayer<'a, K, V> {
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+      let mut map = serializer.serialize_map(Some(self.0.len()))?;
+      for (key, value) in &self.0 {
+        map.serialize_entry(key, value)?;
+      }
+      map.end()
+    }
+  }
+
+  impl<K, V, S> Serialize for ScopeMap<K, V, S>
+  where
+    K: Serialize + Eq + Hash,
+    V: Serialize,
+    S: BuildHasher,
+  {
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+      use serde::ser::SerializeSeq;
+
+      // Track how far up each key's value stack we've walked, since a key's stack holds one
+      // value per layer it's defined in, in the same bottom-to-top order as `self.layers`.
+      // Indexed by the key's position in the underlying IndexMap, so a plain Vec suffices.
+      let mut cursors: Vec<usize> = vec![0; self.map.len()];
+      let mut seq = serializer.serialize_seq(Some(self.layers.len()))?;
+      for layer in &self.layers {
+        let mut pairs = Vec::with_capacity(layer.len());
+        for &index in layer {
+          let (key, stack) = self.map.get_index(index).expect("layer index out of bounds");
+          let pos = &mut cursors[index];
+          pairs.push((key, &stack[*pos]));
+          *pos += 1;
+        }
+        seq.serialize_element(&SerLayer(pairs))?;
+      }
+      seq.end()
+    }
+  }
+
+  impl<'de, K, V, S> Deserialize<'de> for ScopeMap<K, V, S>
+  where
+    K: Deserialize<'de> + Eq + Hash,
+    V: Deserialize<'de>,
+    S: BuildHasher + Default,
+  {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+      struct ScopeMapVisitor<K, V, S>(PhantomData<(K, V, S)>);
+
+      impl<'de, K, V, S> Visitor<'de> for ScopeMapVisitor<K, V, S>
+      where
+        K: Deserialize<'de> + Eq + Hash,
+        V: Deserialize<'de>,
+        S: BuildHasher + Default,
+      {
+        type Value = ScopeMap<K, V, S>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+          formatter.write_str("a sequence of layers, each a map of keys to values")
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+          let mut result: ScopeMap<K, V, S> = ScopeMap::with_hasher(Default::default());
+          let mut is_first_layer = true;
+          while let Some(layer) = seq.next_element::<IndexMap<K, V, S>>()? {
+            if is_first_layer {
+              is_first_layer = false;
+            } else {
+              result.push_layer();
+            }
+            for (key, value) in layer {
+              result.define(key, value);
+            }
+          }
+          Ok(result)
+        }
+      }
+
+      deserializer.deserialize_seq(ScopeMapVisitor(PhantomData))
+    }
+  }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod serde_test {
+  use super::*;
+  #[cfg(not(feature = "std"))]
+  use alloc::string::{String, ToString};
+
+  #[test]
+  fn map_serde_round_trip_preserves_shadowed_layers() {
+    let mut map: ScopeMap<String, i32> = ScopeMap::new();
+    map.define("x".to_string(), 1);
+    map.push_layer();
+    map.define("x".to_string(), 2);
+    map.define("y".to_string(), 3);
+
+    let json = serde_json::to_string(&map).unwrap();
+    let mut round_tripped: ScopeMap<String, i32> = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(Some(&2), round_tripped.get("x"));
+    assert_eq!(Some(&3), round_tripped.get("y"));
+    assert_eq!(2, round_tripped.layer_count());
+
+    round_tripped.pop_layer();
+    assert_eq!(Some(&1), round_tripped.get("x"));
+    assert_eq!(None, round_tripped.get("y"));
+  }
 }
\ No newline at end of file
@@ -1,13 +1,30 @@
-use std::{collections::{hash_map::RandomState}, hash::BuildHasher, hash::Hash, borrow::Borrow};
+#[cfg(not(feature = "std"))]
+extern crate alloc;
+
+use core::{hash::BuildHasher, hash::Hash, borrow::Borrow};
+
+#[cfg(feature = "std")]
+use std::collections::hash_map::RandomState;
+#[cfg(all(not(feature = "std"), feature = "ahash"))]
+use ahash::RandomState;
 
 use crate::ScopeMap;
+use crate::map::Keys;
 
 /// A layered hash set for representing the scopes of variables.
+#[cfg(any(feature = "std", feature = "ahash"))]
 #[derive(Clone)]
 pub struct ScopeSet<T, S: BuildHasher = RandomState> {
   map: ScopeMap<T, (), S>
 }
 
+/// A layered hash set for representing the scopes of variables.
+#[cfg(not(any(feature = "std", feature = "ahash")))]
+#[derive(Clone)]
+pub struct ScopeSet<T, S: BuildHasher> {
+  map: ScopeMap<T, (), S>
+}
+
 impl<T, S: Default + BuildHasher> Default for ScopeSet<T, S> {
   /// Creates a new `ScopeSet` with the default configuration.
   #[inline]
@@ -18,6 +35,7 @@ impl<T, S: Default + BuildHasher> Default for ScopeSet<T, S> {
   }
 }
 
+#[cfg(any(feature = "std", feature = "ahash"))]
 impl<T> ScopeSet<T, RandomState> {
   /// Creates an empty `ScopeSet` with a default hasher and capacity. 
   #[inline]
@@ -121,10 +139,9 @@ impl<T: Eq + Hash, S: BuildHasher> ScopeSet<T, S> {
   ///
   /// Computes in **O(1)** time.
   #[inline]
-  pub fn contains<Q: ?Sized>(&self, key: &Q) -> bool
+  pub fn contains<Q: ?Sized + Eq + Hash>(&self, key: &Q) -> bool
   where
     T: Borrow<Q>,
-    Q: Eq + Hash,
   {
     self.map.contains_key(key)
   }
@@ -133,10 +150,9 @@ impl<T: Eq + Hash, S: BuildHasher> ScopeSet<T, S> {
   //
   /// Computes in **O(1)** time.
   #[inline]
-  pub fn contains_at_top<Q: ?Sized>(&self, key: &Q) -> bool 
+  pub fn contains_at_top<Q: ?Sized + Eq + Hash>(&self, key: &Q) -> bool
   where
     T: Borrow<Q>,
-    Q: Eq + Hash,
   {
     self.map.contains_key_at_top(key)
   }
@@ -148,18 +164,72 @@ impl<T: Eq + Hash, S: BuildHasher> ScopeSet<T, S> {
   ///
   /// Computes in **O(n)** time (worst-case) with respect to layer count.
   #[inline]
-  pub fn depth_of<Q: ?Sized>(&self, key: &Q) -> Option<usize> 
+  pub fn depth_of<Q: ?Sized + Eq + Hash>(&self, key: &Q) -> Option<usize>
   where
     T: Borrow<Q>,
-    Q: Eq + Hash,
   {
     self.map.depth_of(key)
   }
+
+  /// Returns how many times the key is currently shadowed, i.e. the number of live layers below
+  /// the topmost layer containing it that also contain it. Returns `0` for keys that are not
+  /// shadowed or do not exist.
+  #[inline]
+  pub fn shadow_count<Q: ?Sized + Eq + Hash>(&self, key: &Q) -> usize
+  where
+    T: Borrow<Q>,
+  {
+    self.map.shadow_count(key)
+  }
+
+  /// Returns every layer depth (0 = top) at which the specified key is currently live, e.g.
+  /// for "which enclosing scopes define `x`" diagnostics.
+  #[inline]
+  pub fn depths_of<Q: ?Sized + Eq + Hash>(&self, key: &Q) -> impl Iterator<Item = usize> + '_
+  where
+    T: Borrow<Q>,
+  {
+    self.map.iter_all(key).map(|(depth, _)| depth)
+  }
+
+  /// Returns an iterator over all keys currently visible across all layers.
+  #[inline]
+  pub fn iter(&self) -> Iter<'_, T> {
+    Iter {
+      inner: self.map.keys(),
+    }
+  }
+}
+
+impl<'a, T: Eq + Hash, S: BuildHasher> IntoIterator for &'a ScopeSet<T, S> {
+  type Item = &'a T;
+  type IntoIter = Iter<'a, T>;
+
+  #[inline]
+  fn into_iter(self) -> Self::IntoIter {
+    self.iter()
+  }
+}
+
+/// An iterator over all keys of a [`ScopeSet`] that are currently visible across all layers.
+pub struct Iter<'a, T> {
+  inner: Keys<'a, T, ()>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+  type Item = &'a T;
+
+  #[inline]
+  fn next(&mut self) -> Option<Self::Item> {
+    self.inner.next()
+  }
 }
 
 #[cfg(test)]
 mod test {
   use super::*;
+  #[cfg(not(feature = "std"))]
+  use alloc::{vec, vec::Vec, string::String};
 
   #[test]
   fn set_init() {
@@ -220,7 +290,7 @@ mod test {
   #[test]
   fn set_try_pop_first_layer() {
     let mut set: ScopeSet<String> = Default::default();
-    assert_eq!(false, set.pop_layer());
+    assert!(!set.pop_layer());
     assert_eq!(1, set.depth());
   }
 
@@ -258,4 +328,30 @@ mod test {
     assert_eq!(Some(0), set.depth_of("bar"));
     assert_eq!(None, set.depth_of("baz"));
   }
+
+  #[test]
+  fn set_iter() {
+    let mut set = ScopeSet::new();
+    set.define("foo");
+    set.push_layer();
+    set.define("bar");
+    let mut items: Vec<&&str> = set.iter().collect();
+    items.sort_unstable();
+    assert_eq!(vec![&"bar", &"foo"], items);
+  }
+
+  #[test]
+  fn set_shadow_count_and_depths_of() {
+    let mut set = ScopeSet::new();
+    set.define("foo");
+    set.push_layer();
+    set.define("foo");
+    set.push_layer();
+    set.define("bar");
+    assert_eq!(1, set.shadow_count("foo"));
+    assert_eq!(0, set.shadow_count("bar"));
+    assert_eq!(0, set.shadow_count("baz"));
+    assert_eq!(vec![1, 2], set.depths_of("foo").collect::<Vec<_>>());
+    assert_eq!(Vec::<usize>::new(), set.depths_of("baz").collect::<Vec<_>>());
+  }
 }
\ No newline at end of file
@@ -0,0 +1,366 @@
+#[cfg(not(feature = "std"))]
+extern crate alloc;
+
+use core::hash::{BuildHasher, Hash};
+
+#[cfg(feature = "std")]
+use std::rc::{Rc, Weak};
+#[cfg(not(feature = "std"))]
+use alloc::rc::{Rc, Weak};
+
+#[cfg(feature = "std")]
+use std::collections::{hash_map::RandomState, HashMap, HashSet};
+#[cfg(not(feature = "std"))]
+use hashbrown::{HashMap, HashSet};
+#[cfg(all(not(feature = "std"), feature = "ahash"))]
+use ahash::RandomState;
+
+#[cfg(feature = "std")]
+use std::vec::Vec;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use smallvec::{smallvec, SmallVec};
+
+/// A layered map whose keys are held by weak reference, so that bindings for an interned
+/// symbol are reclaimed once its last strong reference is dropped elsewhere.
+///
+/// The layering machinery mirrors [`ScopeMap`](crate::ScopeMap) exactly: each key has a stack
+/// of values, one per layer it's defined in, and each layer tracks the indices it owns. Lookups
+/// hash the probe key up front and only upgrade+compare the handful of slots sharing that hash,
+/// the same trick `weak-table`'s raw tables use, so a live lookup stays close to O(1) instead of
+/// scanning every slot ever allocated.
+#[cfg(any(feature = "std", feature = "ahash"))]
+pub struct WeakScopeMap<K, V, S = RandomState> {
+  entries: Vec<Option<WeakEntry<K, V>>>,
+  free: Vec<usize>,
+  layers: SmallVec<[HashSet<usize>; 1]>,
+  index: HashMap<u64, SmallVec<[usize; 1]>>,
+  hash_builder: S,
+}
+
+#[cfg(not(any(feature = "std", feature = "ahash")))]
+pub struct WeakScopeMap<K, V, S> {
+  entries: Vec<Option<WeakEntry<K, V>>>,
+  free: Vec<usize>,
+  layers: SmallVec<[HashSet<usize>; 1]>,
+  index: HashMap<u64, SmallVec<[usize; 1]>>,
+  hash_builder: S,
+}
+
+struct WeakEntry<K, V> {
+  key: Weak<K>,
+  // The hash of the strong key at insertion time, kept around so an expired entry can be
+  // scrubbed from `index` by `reap` without needing to upgrade (and thus dereference) it.
+  hash: u64,
+  stack: SmallVec<[V; 1]>,
+}
+
+impl<K, V, S: Default> Default for WeakScopeMap<K, V, S> {
+  #[inline]
+  fn default() -> Self {
+    Self::with_hasher(Default::default())
+  }
+}
+
+#[cfg(any(feature = "std", feature = "ahash"))]
+impl<K, V> WeakScopeMap<K, V, RandomState> {
+  /// Creates an empty `WeakScopeMap` with a default hasher.
+  #[inline]
+  pub fn new() -> Self {
+    Default::default()
+  }
+}
+
+impl<K, V, S> WeakScopeMap<K, V, S> {
+  /// Creates an empty `WeakScopeMap` with the specified hasher.
+  #[inline]
+  pub fn with_hasher(hash_builder: S) -> Self {
+    Self {
+      entries: Vec::new(),
+      free: Vec::new(),
+      layers: smallvec![Default::default()],
+      index: HashMap::new(),
+      hash_builder,
+    }
+  }
+
+  /// Returns `true` if the map holds no live keys.
+  #[inline]
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  /// Gets the number of distinct live keys throughout all layers.
+  ///
+  /// Computes in **O(n)** time with respect to the number of slots ever allocated, since expired
+  /// keys are only purged by [`reap`](Self::reap).
+  #[inline]
+  pub fn len(&self) -> usize {
+    self.entries
+      .iter()
+      .filter(|entry| entry.as_ref().is_some_and(|entry| entry.key.strong_count() > 0))
+      .count()
+  }
+
+  /// Gets the number of active layers.
+  #[inline]
+  pub fn layer_count(&self) -> usize {
+    self.layers.len()
+  }
+
+  /// Adds a new, empty layer.
+  ///
+  /// Only reaps expired keys opportunistically, when there's no free slot to reuse, mirroring
+  /// the size-policy threshold [`define`](Self::define) already uses, rather than paying an
+  /// O(n) scan on every single layer push.
+  #[inline]
+  pub fn push_layer(&mut self) {
+    if self.free.is_empty() {
+      self.reap();
+    }
+    self.layers.push(Default::default());
+  }
+
+  /// Removes the topmost layer, if there is more than one.
+  /// Returns `true` if a layer was removed.
+  ///
+  /// Only live entries are affected; expired keys are left for [`reap`](Self::reap) to clear.
+  #[inline]
+  pub fn pop_layer(&mut self) -> bool {
+    if self.layers.len() > 1 {
+      for index in self.layers.pop().unwrap() {
+        if let Some(Some(entry)) = self.entries.get_mut(index) {
+          entry.stack.pop();
+        }
+      }
+      return true;
+    }
+    false
+  }
+
+  /// Removes all live entries in the topmost layer.
+  #[inline]
+  pub fn clear_top(&mut self) {
+    for index in self.layers.last_mut().unwrap().drain() {
+      if let Some(Some(entry)) = self.entries.get_mut(index) {
+        entry.stack.pop();
+      }
+    }
+  }
+
+  /// Removes all entries and additional layers.
+  #[inline]
+  pub fn clear_all(&mut self) {
+    self.entries.clear();
+    self.free.clear();
+    self.layers.clear();
+    self.layers.push(Default::default());
+    self.index.clear();
+  }
+
+  /// Purges entries whose key has expired, scrubbing their indices from every layer's set and
+  /// from the hash index. Returns the number of entries reclaimed.
+  pub fn reap(&mut self) -> usize {
+    let mut removed = 0;
+    for index in 0..self.entries.len() {
+      let expired_hash = match &self.entries[index] {
+        Some(entry) if entry.key.strong_count() == 0 => Some(entry.hash),
+        _ => None,
+      };
+      if let Some(hash) = expired_hash {
+        self.entries[index] = None;
+        self.free.push(index);
+        for layer in &mut self.layers {
+          layer.remove(&index);
+        }
+        if let Some(bucket) = self.index.get_mut(&hash) {
+          bucket.retain(|slot| *slot != index);
+          if bucket.is_empty() {
+            self.index.remove(&hash);
+          }
+        }
+        removed += 1;
+      }
+    }
+    removed
+  }
+
+  /// Alias for [`reap`](Self::reap), matching the name used by `weak-table`.
+  #[inline]
+  pub fn remove_expired(&mut self) -> usize {
+    self.reap()
+  }
+}
+
+impl<K: Eq + Hash, V, S: BuildHasher> WeakScopeMap<K, V, S> {
+  fn hash_of<Q: ?Sized + Hash>(&self, key: &Q) -> u64 {
+    self.hash_builder.hash_one(key)
+  }
+
+  fn find_index(&self, key: &K) -> Option<usize> {
+    let hash = self.hash_of(key);
+    self.index.get(&hash)?.iter().copied().find(|&index| {
+      self.entries[index]
+        .as_ref()
+        .and_then(|entry| entry.key.upgrade())
+        .is_some_and(|strong| &*strong == key)
+    })
+  }
+
+  /// Returns `true` if the map contains a live binding for the specified key in any layer.
+  #[inline]
+  pub fn contains_key(&self, key: &K) -> bool {
+    self.find_index(key).is_some()
+  }
+
+  /// Returns `true` if the map contains a live binding for the specified key at the top layer.
+  #[inline]
+  pub fn contains_key_at_top(&self, key: &K) -> bool {
+    self.find_index(key).is_some_and(|index| self.layers.last().unwrap().contains(&index))
+  }
+
+  /// Gets a reference to the topmost value associated with a key.
+  #[inline]
+  pub fn get(&self, key: &K) -> Option<&V> {
+    let index = self.find_index(key)?;
+    self.entries[index].as_ref()?.stack.last()
+  }
+
+  /// Gets a mutable reference to the topmost value associated with a key.
+  #[inline]
+  pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+    let index = self.find_index(key)?;
+    self.entries[index].as_mut()?.stack.last_mut()
+  }
+
+  /// Adds a value to the topmost layer, keyed by a weak reference to `key`.
+  ///
+  /// If a free slot was reclaimed by a prior [`reap`](Self::reap), it's reused; otherwise, if no
+  /// free slot is available, expired entries are reaped before growing.
+  pub fn define(&mut self, key: &Rc<K>, value: V) {
+    if let Some(index) = self.find_index(key) {
+      let entry = self.entries[index].as_mut().unwrap();
+      let is_new = self.layers.last_mut().unwrap().insert(index);
+      if is_new {
+        entry.stack.push(value);
+      } else {
+        *entry.stack.last_mut().unwrap() = value;
+      }
+      return;
+    }
+
+    if self.free.is_empty() {
+      self.reap();
+    }
+
+    let hash = self.hash_of(key.as_ref());
+    let entry = WeakEntry { key: Rc::downgrade(key), hash, stack: smallvec![value] };
+    let index = match self.free.pop() {
+      Some(index) => {
+        self.entries[index] = Some(entry);
+        index
+      }
+      None => {
+        self.entries.push(Some(entry));
+        self.entries.len() - 1
+      }
+    };
+    self.index.entry(hash).or_default().push(index);
+    self.layers.last_mut().unwrap().insert(index);
+  }
+
+  /// Removes a value from the topmost layer.
+  #[inline]
+  pub fn delete(&mut self, key: &K) -> bool {
+    if let Some(index) = self.find_index(key) {
+      if self.layers.last_mut().unwrap().remove(&index) {
+        if let Some(Some(entry)) = self.entries.get_mut(index) {
+          entry.stack.pop();
+        }
+        return true;
+      }
+    }
+    false
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn weak_define_and_get() {
+    let mut map = WeakScopeMap::new();
+    let key = Rc::new("foo");
+    map.define(&key, 1);
+    assert_eq!(Some(&1), map.get(&key));
+  }
+
+  #[test]
+  fn weak_define_shadows_at_new_layer() {
+    let mut map = WeakScopeMap::new();
+    let key = Rc::new("x");
+    map.define(&key, 1);
+    map.push_layer();
+    map.define(&key, 2);
+    assert_eq!(Some(&2), map.get(&key));
+    assert!(map.contains_key_at_top(&key));
+  }
+
+  #[test]
+  fn weak_pop_layer_reveals_shadowed_value() {
+    let mut map = WeakScopeMap::new();
+    let key = Rc::new("x");
+    map.define(&key, 1);
+    map.push_layer();
+    map.define(&key, 2);
+    map.pop_layer();
+    assert_eq!(Some(&1), map.get(&key));
+  }
+
+  #[test]
+  fn weak_reap_purges_expired_keys() {
+    let mut map = WeakScopeMap::new();
+    let key = Rc::new("foo");
+    map.define(&key, 1);
+    assert_eq!(1, map.len());
+    drop(key);
+    assert_eq!(1, map.reap());
+    assert_eq!(0, map.len());
+  }
+
+  #[test]
+  fn weak_pop_layer_on_expired_key_does_not_panic() {
+    let mut map = WeakScopeMap::new();
+    let key = Rc::new("foo");
+    map.push_layer();
+    map.define(&key, 1);
+    drop(key);
+    // The key is already dangling by the time the layer is popped; pop_layer must not
+    // dereference it, only walk the stored indices.
+    assert!(map.pop_layer());
+    assert_eq!(1, map.layer_count());
+  }
+
+  #[test]
+  fn weak_find_index_survives_hash_collisions_in_the_same_bucket() {
+    // Two different keys that happen to land in the same bucket must both remain reachable;
+    // the bucket holds every colliding slot and resolves ties by upgrading and comparing.
+    #[derive(Default)]
+    struct ConstantHasher;
+    impl core::hash::Hasher for ConstantHasher {
+      fn finish(&self) -> u64 { 0 }
+      fn write(&mut self, _bytes: &[u8]) {}
+    }
+    // Every key hashes to 0, so both keys share one bucket in the hash index.
+    let mut map: WeakScopeMap<&str, i32, core::hash::BuildHasherDefault<ConstantHasher>> =
+      WeakScopeMap::default();
+    let a = Rc::new("a");
+    let b = Rc::new("b");
+    map.define(&a, 1);
+    map.define(&b, 2);
+    assert_eq!(Some(&1), map.get(&a));
+    assert_eq!(Some(&2), map.get(&b));
+  }
+}